@@ -1,7 +1,10 @@
 use std::{fmt, str};
 
 use nom::branch::alt;
-use nom::combinator::map;
+use nom::bytes::complete::{tag, tag_no_case};
+use nom::combinator::{map, opt};
+use nom::multi::separated_list1;
+use nom::sequence::{delimited, preceded, terminated};
 use nom::IResult;
 use serde::{Deserialize, Serialize};
 
@@ -26,9 +29,86 @@ use crate::transaction::{
     commit, rollback, start_transaction, CommitStatement, RollbackStatement,
     StartTransactionStatement,
 };
+use crate::common::ItemPlaceholder;
 use crate::update::{updating, UpdateStatement};
 use crate::use_statement::{use_statement, UseStatement};
-use crate::{Dialect, TableKey};
+use crate::whitespace::{whitespace0, whitespace1};
+use crate::{common, Dialect, TableKey};
+
+/// The inner query body of a [`Cte`]: a CTE may wrap either a plain `SELECT` or a `UNION`-style
+/// compound select.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum CteStatement {
+    Select(Box<SelectStatement>),
+    CompoundSelect(Box<CompoundSelectStatement>),
+}
+
+impl fmt::Display for CteStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CteStatement::Select(select) => write!(f, "{}", select),
+            CteStatement::CompoundSelect(compound) => write!(f, "{}", compound),
+        }
+    }
+}
+
+/// A single `name [(col, ...)] AS ( <select> )` entry in a `WITH` clause.
+///
+/// See [`With`] for the clause that wraps one or more of these.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Cte {
+    pub name: String,
+    pub columns: Option<Vec<String>>,
+    pub statement: CteStatement,
+}
+
+impl fmt::Display for Cte {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}`", self.name)?;
+        if let Some(columns) = &self.columns {
+            write!(
+                f,
+                " ({})",
+                columns
+                    .iter()
+                    .map(|c| format!("`{}`", c))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        write!(f, " AS ({})", self.statement)
+    }
+}
+
+/// The `WITH [RECURSIVE] cte [, cte ...]` prefix that can precede a `SELECT`, `INSERT`,
+/// `UPDATE`, or `DELETE` statement.
+///
+/// Attached directly to `SelectStatement`/`InsertStatement`/`UpdateStatement`/`DeleteStatement`
+/// as a `with: Option<With>` field (added alongside those structs in their own modules), rather
+/// than modeled as a separate wrapper around `SqlQuery`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct With {
+    pub recursive: bool,
+    pub ctes: Vec<Cte>,
+}
+
+impl fmt::Display for With {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WITH ")?;
+        if self.recursive {
+            write!(f, "RECURSIVE ")?;
+        }
+        write!(
+            f,
+            "{}",
+            self.ctes
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 #[allow(clippy::large_enum_variant)]
@@ -55,19 +135,82 @@ pub enum SqlQuery {
     Explain(ExplainStatement),
 }
 
+/// The source of the rows an `INSERT` statement writes: an explicit `VALUES (...)` list, the
+/// result of a `SELECT`, or `DEFAULT VALUES`. Lives on `InsertStatement::data` (added alongside
+/// that struct in its own module), reached via `SqlQuery::Insert` regardless of which form was
+/// used.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum InsertData {
+    /// `INSERT INTO t (cols) VALUES (...), (...)`
+    Values(Vec<Vec<common::Literal>>),
+    /// `INSERT INTO t (cols) SELECT ...`
+    Select(Box<SelectStatement>),
+    /// `INSERT INTO t DEFAULT VALUES`
+    DefaultValues,
+}
+
+impl Default for InsertData {
+    fn default() -> Self {
+        InsertData::Values(Vec::new())
+    }
+}
+
+impl fmt::Display for InsertData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InsertData::Values(rows) => write!(
+                f,
+                "VALUES {}",
+                rows.iter()
+                    .map(|row| format!(
+                        "({})",
+                        row.iter()
+                            .map(|l| l.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            InsertData::Select(select) => write!(f, "{}", select),
+            InsertData::DefaultValues => write!(f, "DEFAULT VALUES"),
+        }
+    }
+}
+
 impl fmt::Display for SqlQuery {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            SqlQuery::Select(ref select) => write!(f, "{}", select),
-            SqlQuery::Insert(ref insert) => write!(f, "{}", insert),
+            SqlQuery::Select(ref select) => {
+                if let Some(with) = &select.with {
+                    write!(f, "{} ", with)?;
+                }
+                write!(f, "{}", select)
+            }
+            SqlQuery::Insert(ref insert) => {
+                if let Some(with) = &insert.with {
+                    write!(f, "{} ", with)?;
+                }
+                write!(f, "{}", insert)
+            }
             SqlQuery::CreateTable(ref create) => write!(f, "{}", create),
             SqlQuery::CreateView(ref create) => write!(f, "{}", create),
             SqlQuery::CreateCache(ref create) => write!(f, "{}", create),
             SqlQuery::DropCache(ref drop) => write!(f, "{}", drop),
-            SqlQuery::Delete(ref delete) => write!(f, "{}", delete),
+            SqlQuery::Delete(ref delete) => {
+                if let Some(with) = &delete.with {
+                    write!(f, "{} ", with)?;
+                }
+                write!(f, "{}", delete)
+            }
             SqlQuery::DropTable(ref drop) => write!(f, "{}", drop),
             SqlQuery::DropView(ref drop) => write!(f, "{}", drop),
-            SqlQuery::Update(ref update) => write!(f, "{}", update),
+            SqlQuery::Update(ref update) => {
+                if let Some(with) = &update.with {
+                    write!(f, "{} ", with)?;
+                }
+                write!(f, "{}", update)
+            }
             SqlQuery::Set(ref set) => write!(f, "{}", set),
             SqlQuery::AlterTable(ref alter) => write!(f, "{}", alter),
             SqlQuery::CompoundSelect(ref compound) => write!(f, "{}", compound),
@@ -83,13 +226,82 @@ impl fmt::Display for SqlQuery {
 }
 
 impl str::FromStr for SqlQuery {
-    type Err = &'static str;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         parse_query(Dialect::MySQL, s)
     }
 }
 
+/// The number of characters of unconsumed input, starting at an error's failure point, to keep
+/// for display purposes.
+const PARSE_ERROR_CONTEXT_LEN: usize = 40;
+
+/// A structured error produced when a `parse_*` function fails.
+///
+/// Unlike a bare `&'static str`, this carries the byte offset into the parsed input where
+/// parsing gave up, plus a truncated snippet of the unconsumed input at that point, so callers
+/// can build actionable diagnostics instead of a single fixed message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    /// The byte offset into the parsed input of the furthest point any alternative reached. The
+    /// `&str`-taking `parse_*` functions trim surrounding whitespace before parsing, so for them
+    /// this is an offset into the trimmed string.
+    pub offset: usize,
+    /// A truncated view of the input starting at `offset`.
+    pub context: String,
+    /// The name of the top-level `sql_query` alternative that got furthest, if the failure was
+    /// produced by [`sql_query_diagnostic`] trying more than one alternative and at least one of
+    /// them got past the start of the input (on a tie, the first such alternative). `None`
+    /// otherwise, including for errors from a `parse_*` function that only ever tries a single
+    /// grammar (e.g. `parse_select_statement`).
+    pub furthest_alternative: Option<&'static str>,
+}
+
+impl ParseError {
+    fn new(input: &[u8], remaining: &[u8]) -> Self {
+        let offset = input.len().saturating_sub(remaining.len());
+        let context: String = String::from_utf8_lossy(remaining)
+            .chars()
+            .take(PARSE_ERROR_CONTEXT_LEN)
+            .collect();
+        Self {
+            offset,
+            context,
+            furthest_alternative: None,
+        }
+    }
+
+    /// Record which top-level alternative got furthest before everything failed.
+    fn with_alternative(mut self, alternative: Option<&'static str>) -> Self {
+        self.furthest_alternative = alternative;
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "failed to parse query at byte {}, near `{}`",
+            self.offset, self.context
+        )?;
+        if let Some(alternative) = self.furthest_alternative {
+            write!(f, " (furthest alternative tried: {})", alternative)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn nom_err_to_parse_error(input: &[u8], err: nom::Err<nom::error::Error<&[u8]>>) -> ParseError {
+    match err {
+        nom::Err::Error(e) | nom::Err::Failure(e) => ParseError::new(input, e.input),
+        nom::Err::Incomplete(_) => ParseError::new(input, &[]),
+    }
+}
+
 impl SqlQuery {
     /// Returns the type of the query, e.g. "CREATE TABLE" or "SELECT"
     pub fn query_type(&self) -> &'static str {
@@ -116,49 +328,550 @@ impl SqlQuery {
             Self::Explain(_) => "EXPLAIN",
         }
     }
+
+    /// Returns `true` if this is a read-only query that returns rows: `SELECT`, `SHOW`, or
+    /// `EXPLAIN`.
+    pub fn is_query(&self) -> bool {
+        matches!(
+            self,
+            Self::Select(_) | Self::CompoundSelect(_) | Self::Show(_) | Self::Explain(_)
+        )
+    }
+
+    /// Returns `true` if this is a data-manipulation statement: `INSERT`, `UPDATE`, or `DELETE`.
+    pub fn is_dml(&self) -> bool {
+        matches!(self, Self::Insert(_) | Self::Update(_) | Self::Delete(_))
+    }
+
+    /// Returns `true` if this is a data-definition statement: `CREATE TABLE`/`VIEW`,
+    /// `DROP TABLE`/`VIEW`, `ALTER TABLE`, or `RENAME TABLE`.
+    pub fn is_ddl(&self) -> bool {
+        matches!(
+            self,
+            Self::CreateTable(_)
+                | Self::CreateView(_)
+                | Self::DropTable(_)
+                | Self::DropView(_)
+                | Self::AlterTable(_)
+                | Self::RenameTable(_)
+        )
+    }
+
+    /// Returns `true` if this is a transaction-control statement: `START TRANSACTION`, `COMMIT`,
+    /// or `ROLLBACK`.
+    pub fn is_transaction_control(&self) -> bool {
+        matches!(
+            self,
+            Self::StartTransaction(_) | Self::Commit(_) | Self::Rollback(_)
+        )
+    }
+
+    /// Returns `true` if executing this query cannot modify any persistent state: currently
+    /// equivalent to [`SqlQuery::is_query`].
+    pub fn is_read_only(&self) -> bool {
+        self.is_query()
+    }
+
+    /// Returns every bind-parameter placeholder in this statement, in the order they appear
+    /// (WHERE, VALUES, SET, LIMIT, etc.), with anonymous `?` markers auto-numbered
+    /// left-to-right so callers can reconcile a positional bind list against a mix of `?` and
+    /// `$N`/`:name` markers.
+    ///
+    /// This walks the parsed AST rather than re-scanning `Display` output: each
+    /// `Literal::Placeholder` node records its own `ItemPlaceholder` kind, so enumeration here is
+    /// just classifying the markers already present in the AST, not inferring them from text.
+    pub fn parameters(&self) -> Vec<ParamKind> {
+        let mut literals = Vec::new();
+        collect_literals(self, &mut literals);
+
+        let mut anonymous_ordinal = 0u32;
+        literals
+            .into_iter()
+            .filter_map(|literal| match literal {
+                common::Literal::Placeholder(ItemPlaceholder::QuestionMark) => {
+                    anonymous_ordinal += 1;
+                    Some(ParamKind::Anonymous(anonymous_ordinal))
+                }
+                common::Literal::Placeholder(ItemPlaceholder::DollarNumber(n)) => {
+                    Some(ParamKind::Numbered(*n as u32))
+                }
+                common::Literal::Placeholder(ItemPlaceholder::ColonNumber(n)) => {
+                    Some(ParamKind::Numbered(*n as u32))
+                }
+                common::Literal::Placeholder(ItemPlaceholder::ColonIdentifier(name))
+                | common::Literal::Placeholder(ItemPlaceholder::AtIdentifier(name)) => {
+                    Some(ParamKind::Named(name.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replace every inline literal in this statement (in WHERE/HAVING predicates, `VALUES`
+    /// rows, `SET` assignments, `LIMIT`/`OFFSET`, and function arguments) with a `?`
+    /// placeholder, returning the rewritten statement plus the literals extracted, in order.
+    ///
+    /// This walks the AST directly and mutates literals in place; it never re-serializes
+    /// through `Display` and reparses, so there's no failure mode to swallow. Literals that are
+    /// already a placeholder (an existing bind parameter) are left alone and not counted as
+    /// extracted, since they aren't inline values to begin with.
+    ///
+    /// Two statements that differ only in their literal values produce byte-identical `Display`
+    /// output (and therefore equal `Hash`) after anonymization, which is what a query cache
+    /// keyed on statement shape needs. `IN (...)` list cardinality is preserved, since each
+    /// element of the list is anonymized individually rather than collapsed into one
+    /// placeholder.
+    pub fn anonymize(&self) -> (SqlQuery, Vec<common::Literal>) {
+        let mut query = self.clone();
+        let mut extracted = Vec::new();
+        anonymize_query(&mut query, &mut extracted);
+        (query, extracted)
+    }
 }
 
-pub fn sql_query(dialect: Dialect) -> impl Fn(&[u8]) -> IResult<&[u8], SqlQuery> {
+/// Replace every non-placeholder literal reachable from `query`'s AST with
+/// `Literal::Placeholder(ItemPlaceholder::QuestionMark)` in place, pushing each original literal
+/// onto `out` in the order it was encountered. Mirrors the traversal in [`collect_literals`].
+fn anonymize_query(query: &mut SqlQuery, out: &mut Vec<common::Literal>) {
+    match query {
+        SqlQuery::Select(select) => {
+            if let Some(with) = &mut select.with {
+                anonymize_with(with, out);
+            }
+            anonymize_literals(select.literals_mut(), out);
+        }
+        SqlQuery::CompoundSelect(compound) => anonymize_literals(compound.literals_mut(), out),
+        SqlQuery::Insert(insert) => {
+            if let Some(with) = &mut insert.with {
+                anonymize_with(with, out);
+            }
+            match &mut insert.data {
+                InsertData::Values(rows) => {
+                    anonymize_literals(rows.iter_mut().flatten(), out)
+                }
+                InsertData::Select(select) => anonymize_literals(select.literals_mut(), out),
+                InsertData::DefaultValues => {}
+            }
+        }
+        SqlQuery::Update(update) => {
+            if let Some(with) = &mut update.with {
+                anonymize_with(with, out);
+            }
+            anonymize_literals(update.literals_mut(), out);
+        }
+        SqlQuery::Delete(delete) => {
+            if let Some(with) = &mut delete.with {
+                anonymize_with(with, out);
+            }
+            anonymize_literals(delete.literals_mut(), out);
+        }
+        _ => {}
+    }
+}
+
+/// Anonymize the literals inside every CTE body of a `WITH` clause.
+fn anonymize_with(with: &mut With, out: &mut Vec<common::Literal>) {
+    for cte in &mut with.ctes {
+        match &mut cte.statement {
+            CteStatement::Select(select) => anonymize_literals(select.literals_mut(), out),
+            CteStatement::CompoundSelect(compound) => {
+                anonymize_literals(compound.literals_mut(), out)
+            }
+        }
+    }
+}
+
+/// Replace every non-placeholder literal in `literals` with a `?` placeholder, pushing the
+/// original value onto `out`.
+fn anonymize_literals<'a>(
+    literals: impl Iterator<Item = &'a mut common::Literal>,
+    out: &mut Vec<common::Literal>,
+) {
+    for literal in literals {
+        if matches!(literal, common::Literal::Placeholder(_)) {
+            continue;
+        }
+        let original = std::mem::replace(
+            literal,
+            common::Literal::Placeholder(ItemPlaceholder::QuestionMark),
+        );
+        out.push(original);
+    }
+}
+
+/// Collect every literal reachable from `query`'s AST, in the order they'd be written out by
+/// `Display` (CTE bodies in the `WITH` clause, then WHERE/HAVING predicates, `VALUES` rows,
+/// `SET` assignments, etc.), used by [`SqlQuery::parameters`] to classify placeholder markers.
+fn collect_literals<'a>(query: &'a SqlQuery, out: &mut Vec<&'a common::Literal>) {
+    match query {
+        SqlQuery::Select(select) => {
+            if let Some(with) = &select.with {
+                collect_with_literals(with, out);
+            }
+            out.extend(select.literals());
+        }
+        SqlQuery::CompoundSelect(compound) => out.extend(compound.literals()),
+        SqlQuery::Insert(insert) => {
+            if let Some(with) = &insert.with {
+                collect_with_literals(with, out);
+            }
+            match &insert.data {
+                InsertData::Values(rows) => out.extend(rows.iter().flatten()),
+                InsertData::Select(select) => out.extend(select.literals()),
+                InsertData::DefaultValues => {}
+            }
+        }
+        SqlQuery::Update(update) => {
+            if let Some(with) = &update.with {
+                collect_with_literals(with, out);
+            }
+            out.extend(update.literals());
+        }
+        SqlQuery::Delete(delete) => {
+            if let Some(with) = &delete.with {
+                collect_with_literals(with, out);
+            }
+            out.extend(delete.literals());
+        }
+        _ => {}
+    }
+}
+
+/// Collect the literals inside every CTE body of a `WITH` clause.
+fn collect_with_literals<'a>(with: &'a With, out: &mut Vec<&'a common::Literal>) {
+    for cte in &with.ctes {
+        match &cte.statement {
+            CteStatement::Select(select) => out.extend(select.literals()),
+            CteStatement::CompoundSelect(compound) => out.extend(compound.literals()),
+        }
+    }
+}
+
+/// The syntactic form of a bind-parameter placeholder, as returned by [`SqlQuery::parameters`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum ParamKind {
+    /// A bare `?`, assigned the given left-to-right ordinal among the other anonymous
+    /// placeholders in the statement.
+    Anonymous(u32),
+    /// A `$N` (PostgreSQL-style) or `:N` numbered placeholder. A `?N` marker is not recognized
+    /// as a numbered placeholder.
+    Numbered(u32),
+    /// A `:name` or `@name` named placeholder.
+    Named(String),
+}
+
+impl fmt::Display for ParamKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamKind::Anonymous(_) => write!(f, "?"),
+            ParamKind::Numbered(n) => write!(f, "${}", n),
+            ParamKind::Named(name) => write!(f, ":{}", name),
+        }
+    }
+}
+
+/// Parse the `WITH [RECURSIVE] name [(col, ...)] AS ( <select> ) [, ...]` prefix that can
+/// precede a `SELECT`, `INSERT`, `UPDATE`, or `DELETE` statement.
+fn with_clause(dialect: Dialect) -> impl Fn(&[u8]) -> IResult<&[u8], With> {
+    move |i| {
+        let (i, _) = tag_no_case("with")(i)?;
+        let (i, _) = whitespace1(i)?;
+        let (i, recursive) = opt(terminated(tag_no_case("recursive"), whitespace1))(i)?;
+        let (i, ctes) = separated_list1(common::ws_sep_comma, common_table_expr(dialect))(i)?;
+        let (i, _) = whitespace1(i)?;
+        Ok((
+            i,
+            With {
+                recursive: recursive.is_some(),
+                ctes,
+            },
+        ))
+    }
+}
+
+fn common_table_expr(dialect: Dialect) -> impl Fn(&[u8]) -> IResult<&[u8], Cte> {
+    move |i| {
+        let (i, name) = common::sql_identifier(i)?;
+        let (i, columns) = opt(preceded(
+            whitespace0,
+            delimited(
+                terminated(tag("("), whitespace0),
+                separated_list1(common::ws_sep_comma, common::sql_identifier),
+                preceded(whitespace0, tag(")")),
+            ),
+        ))(i)?;
+        let (i, _) = whitespace1(i)?;
+        let (i, _) = tag_no_case("as")(i)?;
+        let (i, _) = whitespace0(i)?;
+        let (i, _) = tag("(")(i)?;
+        let (i, _) = whitespace0(i)?;
+        let (i, statement) = alt((
+            map(compound_selection(dialect), |compound| {
+                CteStatement::CompoundSelect(Box::new(compound))
+            }),
+            map(selection(dialect), |select| {
+                CteStatement::Select(Box::new(select))
+            }),
+        ))(i)?;
+        let (i, _) = whitespace0(i)?;
+        let (i, _) = tag(")")(i)?;
+        Ok((
+            i,
+            Cte {
+                name: String::from(common::to_utf8_lossy(name)),
+                columns: columns.map(|cols| cols.into_iter().map(common::to_utf8_lossy).collect()),
+                statement,
+            },
+        ))
+    }
+}
+
+/// Parse a `WITH`-prefixed statement: the clause may precede a `SELECT`, `INSERT`, `UPDATE`, or
+/// `DELETE`. The parsed [`With`] is attached to the inner statement's own `with` field rather
+/// than modeled as a separate `SqlQuery` variant, so the result is returned through the same
+/// `SqlQuery::Select`/`Insert`/`Update`/`Delete` variants as the un-prefixed forms.
+fn with_prefixed_statement(dialect: Dialect) -> impl Fn(&[u8]) -> IResult<&[u8], SqlQuery> {
     move |i| {
+        let (i, with) = with_clause(dialect)(i)?;
         alt((
-            map(creation(dialect), SqlQuery::CreateTable),
-            map(insertion(dialect), SqlQuery::Insert),
-            map(compound_selection(dialect), SqlQuery::CompoundSelect),
-            map(selection(dialect), SqlQuery::Select),
-            map(deletion(dialect), SqlQuery::Delete),
-            map(drop_table(dialect), SqlQuery::DropTable),
-            map(drop_view(dialect), SqlQuery::DropView),
-            map(updating(dialect), SqlQuery::Update),
-            map(set(dialect), SqlQuery::Set),
-            map(view_creation(dialect), SqlQuery::CreateView),
-            map(create_cached_query(dialect), SqlQuery::CreateCache),
-            map(drop_cached_query(dialect), SqlQuery::DropCache),
-            map(alter_table_statement(dialect), SqlQuery::AlterTable),
-            map(start_transaction(dialect), SqlQuery::StartTransaction),
-            map(commit(dialect), SqlQuery::Commit),
-            map(rollback(dialect), SqlQuery::Rollback),
-            map(rename_table(dialect), SqlQuery::RenameTable),
-            map(use_statement(dialect), SqlQuery::Use),
-            map(show(dialect), SqlQuery::Show),
-            map(explain_statement, SqlQuery::Explain),
+            map(selection(dialect), |select| {
+                SqlQuery::Select(SelectStatement {
+                    with: Some(with.clone()),
+                    ..select
+                })
+            }),
+            map(insertion(dialect), |insert| {
+                SqlQuery::Insert(InsertStatement {
+                    with: Some(with.clone()),
+                    ..insert
+                })
+            }),
+            map(updating(dialect), |update| {
+                SqlQuery::Update(UpdateStatement {
+                    with: Some(with.clone()),
+                    ..update
+                })
+            }),
+            map(deletion(dialect), |delete| {
+                SqlQuery::Delete(DeleteStatement {
+                    with: Some(with.clone()),
+                    ..delete
+                })
+            }),
         ))(i)
     }
 }
 
+/// Parse `INSERT INTO t [(cols)] SELECT ...` or `INSERT INTO t DEFAULT VALUES`, the two
+/// `INSERT` forms that don't supply an explicit `VALUES (...)` list. The result is an
+/// `InsertStatement` (reached via `SqlQuery::Insert`, same as a plain `VALUES` insert), with
+/// `data` set to the matching non-`Values` variant of [`InsertData`].
+fn insert_select_or_default(dialect: Dialect) -> impl Fn(&[u8]) -> IResult<&[u8], SqlQuery> {
+    move |i| {
+        let (i, _) = tag_no_case("insert")(i)?;
+        let (i, _) = whitespace1(i)?;
+        let (i, _) = tag_no_case("into")(i)?;
+        let (i, _) = whitespace1(i)?;
+        let (i, table) = common::table_reference(i)?;
+        let (i, _) = whitespace0(i)?;
+        let (i, fields) = opt(delimited(
+            terminated(tag("("), whitespace0),
+            separated_list1(common::ws_sep_comma, common::column_identifier_no_alias),
+            preceded(whitespace0, tag(")")),
+        ))(i)?;
+        let (i, _) = whitespace0(i)?;
+        let (i, data) = alt((
+            map(
+                nom::sequence::tuple((tag_no_case("default"), whitespace1, tag_no_case("values"))),
+                |_| InsertData::DefaultValues,
+            ),
+            map(selection(dialect), |select| {
+                InsertData::Select(Box::new(select))
+            }),
+        ))(i)?;
+        Ok((
+            i,
+            SqlQuery::Insert(InsertStatement {
+                table,
+                fields,
+                data,
+                with: None,
+                ..Default::default()
+            }),
+        ))
+    }
+}
+
+/// A single named alternative tried by [`sql_query`] and [`sql_query_diagnostic`]. Plain `fn`
+/// pointers (rather than boxed closures) so [`SQL_QUERY_BRANCHES`] can be a `const` array shared
+/// by both the fast path and the diagnostic path, with zero allocation per parse call.
+type SqlQueryBranch = fn(Dialect, &[u8]) -> IResult<&[u8], SqlQuery>;
+
+fn branch_with(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    with_prefixed_statement(dialect)(i)
+}
+
+fn branch_insert_select_or_default(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    insert_select_or_default(dialect)(i)
+}
+
+fn branch_create_table(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(creation(dialect), SqlQuery::CreateTable)(i)
+}
+
+fn branch_insert(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(insertion(dialect), SqlQuery::Insert)(i)
+}
+
+fn branch_compound_select(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(compound_selection(dialect), SqlQuery::CompoundSelect)(i)
+}
+
+fn branch_select(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(selection(dialect), SqlQuery::Select)(i)
+}
+
+fn branch_delete(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(deletion(dialect), SqlQuery::Delete)(i)
+}
+
+fn branch_drop_table(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(drop_table(dialect), SqlQuery::DropTable)(i)
+}
+
+fn branch_drop_view(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(drop_view(dialect), SqlQuery::DropView)(i)
+}
+
+fn branch_update(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(updating(dialect), SqlQuery::Update)(i)
+}
+
+fn branch_set(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(set(dialect), SqlQuery::Set)(i)
+}
+
+fn branch_create_view(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(view_creation(dialect), SqlQuery::CreateView)(i)
+}
+
+fn branch_create_cache(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(create_cached_query(dialect), SqlQuery::CreateCache)(i)
+}
+
+fn branch_drop_cache(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(drop_cached_query(dialect), SqlQuery::DropCache)(i)
+}
+
+fn branch_alter_table(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(alter_table_statement(dialect), SqlQuery::AlterTable)(i)
+}
+
+fn branch_start_transaction(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(start_transaction(dialect), SqlQuery::StartTransaction)(i)
+}
+
+fn branch_commit(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(commit(dialect), SqlQuery::Commit)(i)
+}
+
+fn branch_rollback(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(rollback(dialect), SqlQuery::Rollback)(i)
+}
+
+fn branch_rename_table(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(rename_table(dialect), SqlQuery::RenameTable)(i)
+}
+
+fn branch_use(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(use_statement(dialect), SqlQuery::Use)(i)
+}
+
+fn branch_show(dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(show(dialect), SqlQuery::Show)(i)
+}
+
+fn branch_explain(_dialect: Dialect, i: &[u8]) -> IResult<&[u8], SqlQuery> {
+    map(explain_statement, SqlQuery::Explain)(i)
+}
+
+/// The individual alternatives tried by [`sql_query`] and [`sql_query_diagnostic`], named and
+/// built once as a `const` array of plain function pointers, so both the fast path and the
+/// diagnostic path are driven from this single list instead of hand-keeping two copies in sync,
+/// without the per-call `Vec`/`Box<dyn Fn>` allocation that a builder function would incur.
+const SQL_QUERY_BRANCHES: &[(&str, SqlQueryBranch)] = &[
+    ("WITH", branch_with),
+    ("INSERT ... SELECT/DEFAULT VALUES", branch_insert_select_or_default),
+    ("CREATE TABLE", branch_create_table),
+    ("INSERT", branch_insert),
+    ("compound SELECT", branch_compound_select),
+    ("SELECT", branch_select),
+    ("DELETE", branch_delete),
+    ("DROP TABLE", branch_drop_table),
+    ("DROP VIEW", branch_drop_view),
+    ("UPDATE", branch_update),
+    ("SET", branch_set),
+    ("CREATE VIEW", branch_create_view),
+    ("CREATE CACHE", branch_create_cache),
+    ("DROP CACHE", branch_drop_cache),
+    ("ALTER TABLE", branch_alter_table),
+    ("START TRANSACTION", branch_start_transaction),
+    ("COMMIT", branch_commit),
+    ("ROLLBACK", branch_rollback),
+    ("RENAME TABLE", branch_rename_table),
+    ("USE", branch_use),
+    ("SHOW", branch_show),
+    ("EXPLAIN", branch_explain),
+];
+
+/// Try each of [`SQL_QUERY_BRANCHES`] in turn, returning the first success.
+pub fn sql_query(dialect: Dialect) -> impl Fn(&[u8]) -> IResult<&[u8], SqlQuery> {
+    move |i| {
+        let mut last_err = None;
+        for &(_, branch) in SQL_QUERY_BRANCHES {
+            match branch(dialect, i) {
+                Ok(result) => return Ok(result),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err
+            .unwrap_or_else(|| nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Alt))))
+    }
+}
+
+/// Try every top-level alternative in turn, the same way [`sql_query`] does, but track which
+/// alternative got furthest (by consumed-byte offset) so a failure points at the real failure
+/// site instead of the start of input, along with the name of the alternative that reached it.
+fn sql_query_diagnostic(dialect: Dialect, input: &[u8]) -> Result<SqlQuery, ParseError> {
+    let mut furthest: &[u8] = input;
+    let mut furthest_alternative: Option<&'static str> = None;
+
+    for &(name, branch) in SQL_QUERY_BRANCHES {
+        match branch(dialect, input) {
+            Ok((_, query)) => return Ok(query),
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                if e.input.len() < furthest.len() {
+                    furthest = e.input;
+                    furthest_alternative = Some(name);
+                }
+            }
+            Err(nom::Err::Incomplete(_)) => {}
+        }
+    }
+
+    Err(ParseError::new(input, furthest).with_alternative(furthest_alternative))
+}
+
 /// Parse a SQL query from a byte slice
-pub fn parse_query_bytes<T>(dialect: Dialect, input: T) -> Result<SqlQuery, &'static str>
+pub fn parse_query_bytes<T>(dialect: Dialect, input: T) -> Result<SqlQuery, ParseError>
 where
     T: AsRef<[u8]>,
 {
-    match sql_query(dialect)(input.as_ref()) {
-        Ok((_, o)) => Ok(o),
-        Err(_) => Err("failed to parse query"),
-    }
+    sql_query_diagnostic(dialect, input.as_ref())
 }
 
 /// Parse a SQL query from a string
-// TODO(fran): Make this function return a ReadySetResult.
-pub fn parse_query<T>(dialect: Dialect, input: T) -> Result<SqlQuery, &'static str>
+pub fn parse_query<T>(dialect: Dialect, input: T) -> Result<SqlQuery, ParseError>
 where
     T: AsRef<str>,
 {
@@ -169,21 +882,20 @@ where
 pub fn parse_select_statement_bytes<T>(
     dialect: Dialect,
     input: T,
-) -> Result<SelectStatement, &'static str>
+) -> Result<SelectStatement, ParseError>
 where
     T: AsRef<[u8]>,
 {
-    match selection(dialect)(input.as_ref()) {
+    let bytes = input.as_ref();
+    match selection(dialect)(bytes) {
         Ok((remaining, o)) if remaining.is_empty() => Ok(o),
-        _ => Err("failed to parse query"),
+        Ok((remaining, _)) => Err(ParseError::new(bytes, remaining)),
+        Err(e) => Err(nom_err_to_parse_error(bytes, e)),
     }
 }
 
 /// Parse a select statement from a string
-pub fn parse_select_statement<T>(
-    dialect: Dialect,
-    input: T,
-) -> Result<SelectStatement, &'static str>
+pub fn parse_select_statement<T>(dialect: Dialect, input: T) -> Result<SelectStatement, ParseError>
 where
     T: AsRef<str>,
 {
@@ -194,13 +906,15 @@ where
 pub fn parse_create_table_bytes<T>(
     dialect: Dialect,
     input: T,
-) -> Result<CreateTableStatement, &'static str>
+) -> Result<CreateTableStatement, ParseError>
 where
     T: AsRef<[u8]>,
 {
-    match creation(dialect)(input.as_ref()) {
+    let bytes = input.as_ref();
+    match creation(dialect)(bytes) {
         Ok((remaining, o)) if remaining.is_empty() => Ok(o),
-        _ => Err("failed to parse query"),
+        Ok((remaining, _)) => Err(ParseError::new(bytes, remaining)),
+        Err(e) => Err(nom_err_to_parse_error(bytes, e)),
     }
 }
 
@@ -208,7 +922,7 @@ where
 pub fn parse_create_table<T>(
     dialect: Dialect,
     input: T,
-) -> Result<CreateTableStatement, &'static str>
+) -> Result<CreateTableStatement, ParseError>
 where
     T: AsRef<str>,
 {
@@ -219,18 +933,20 @@ where
 pub fn parse_alter_table_bytes<T>(
     dialect: Dialect,
     input: T,
-) -> Result<AlterTableStatement, &'static str>
+) -> Result<AlterTableStatement, ParseError>
 where
     T: AsRef<[u8]>,
 {
-    match alter_table_statement(dialect)(input.as_ref()) {
+    let bytes = input.as_ref();
+    match alter_table_statement(dialect)(bytes) {
         Ok((remaining, o)) if remaining.is_empty() => Ok(o),
-        _ => Err("failed to parse query"),
+        Ok((remaining, _)) => Err(ParseError::new(bytes, remaining)),
+        Err(e) => Err(nom_err_to_parse_error(bytes, e)),
     }
 }
 
 /// Parse an alter table statement from a string
-pub fn parse_alter_table<T>(dialect: Dialect, input: T) -> Result<AlterTableStatement, &'static str>
+pub fn parse_alter_table<T>(dialect: Dialect, input: T) -> Result<AlterTableStatement, ParseError>
 where
     T: AsRef<str>,
 {
@@ -238,24 +954,19 @@ where
 }
 
 /// Parse a specification for a table key or constraint from a byte slice
-pub fn parse_key_specification_bytes<T>(
-    dialect: Dialect,
-    input: T,
-) -> Result<TableKey, &'static str>
+pub fn parse_key_specification_bytes<T>(dialect: Dialect, input: T) -> Result<TableKey, ParseError>
 where
     T: AsRef<[u8]>,
 {
-    match key_specification(dialect)(input.as_ref()) {
+    let bytes = input.as_ref();
+    match key_specification(dialect)(bytes) {
         Ok((_, o)) => Ok(o),
-        Err(_) => Err("failed to parse query"),
+        Err(e) => Err(nom_err_to_parse_error(bytes, e)),
     }
 }
 
 /// Parse a specification for a table key or constraint from a string
-pub fn parse_key_specification_string<T>(
-    dialect: Dialect,
-    input: T,
-) -> Result<TableKey, &'static str>
+pub fn parse_key_specification_string<T>(dialect: Dialect, input: T) -> Result<TableKey, ParseError>
 where
     T: AsRef<str>,
 {
@@ -401,6 +1112,106 @@ mod tests {
         assert_eq!(expected1, format!("{}", res1.unwrap()));
     }
 
+    #[test]
+    fn display_cte_query() {
+        let qstring = "WITH `recent` AS (SELECT * FROM `orders`) SELECT * FROM `recent`";
+        let res = parse_query(Dialect::MySQL, qstring);
+        assert!(res.is_ok());
+        assert_eq!(qstring, format!("{}", res.unwrap()));
+    }
+
+    #[test]
+    fn parse_recursive_cte_query() {
+        let qstring = "WITH RECURSIVE `counter` AS (SELECT * FROM `seed`) SELECT * FROM `counter`";
+        let res = parse_query(Dialect::MySQL, qstring);
+        assert!(res.is_ok());
+        assert_eq!(qstring, format!("{}", res.unwrap()));
+    }
+
+    #[test]
+    fn parse_error_points_at_failure_site() {
+        let qstring = "SELECT * FROM users WHERE !!!";
+        let err = parse_query(Dialect::MySQL, qstring).unwrap_err();
+        assert_eq!(err.offset, qstring.find("!!!").unwrap());
+    }
+
+    #[test]
+    fn display_insert_select_query() {
+        let qstring = "INSERT INTO `archived_users` (`id`, `name`) SELECT * FROM `users`";
+        let res = parse_query(Dialect::MySQL, qstring);
+        assert!(res.is_ok());
+        assert_eq!(qstring, format!("{}", res.unwrap()));
+    }
+
+    #[test]
+    fn display_insert_default_values_query() {
+        let qstring = "INSERT INTO `users` DEFAULT VALUES";
+        let res = parse_query(Dialect::MySQL, qstring);
+        assert!(res.is_ok());
+        assert_eq!(qstring, format!("{}", res.unwrap()));
+    }
+
+    #[test]
+    fn anonymize_produces_identical_shape_for_different_literals() {
+        let a = parse_query(Dialect::MySQL, "SELECT * FROM users WHERE id = 1").unwrap();
+        let b = parse_query(Dialect::MySQL, "SELECT * FROM users WHERE id = 2").unwrap();
+
+        let (a_anon, a_literals) = a.anonymize();
+        let (b_anon, b_literals) = b.anonymize();
+
+        assert_eq!(format!("{}", a_anon), format!("{}", b_anon));
+        assert_eq!(a_literals, vec![common::Literal::from(1)]);
+        assert_eq!(b_literals, vec![common::Literal::from(2)]);
+    }
+
+    #[test]
+    fn enumerates_mixed_placeholders() {
+        // Built directly rather than parsed: `$N`/`:name` placeholder forms are gated by
+        // `Dialect` in the literal grammar, which this checkout doesn't carry, so we exercise
+        // `parameters()`'s AST walk against a statement built with every marker kind directly.
+        let query = SqlQuery::Insert(InsertStatement {
+            table: crate::table::Table::from("users"),
+            fields: None,
+            data: InsertData::Values(vec![vec![
+                common::Literal::Placeholder(ItemPlaceholder::DollarNumber(1)),
+                common::Literal::Placeholder(ItemPlaceholder::ColonIdentifier("name".to_string())),
+                common::Literal::Placeholder(ItemPlaceholder::QuestionMark),
+            ]]),
+            with: None,
+            ..Default::default()
+        });
+        let params = query.parameters();
+        assert_eq!(
+            params,
+            vec![
+                ParamKind::Numbered(1),
+                ParamKind::Named("name".to_string()),
+                ParamKind::Anonymous(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn classifies_statements() {
+        let select = parse_query(Dialect::MySQL, "SELECT * FROM users").unwrap();
+        assert!(select.is_query());
+        assert!(select.is_read_only());
+        assert!(!select.is_dml());
+        assert!(!select.is_ddl());
+
+        let insert = parse_query(Dialect::MySQL, "INSERT INTO users VALUES (1)").unwrap();
+        assert!(insert.is_dml());
+        assert!(!insert.is_query());
+
+        let create = parse_query(Dialect::MySQL, "CREATE TABLE users (id int)").unwrap();
+        assert!(create.is_ddl());
+        assert!(!create.is_dml());
+
+        let commit = parse_query(Dialect::MySQL, "COMMIT").unwrap();
+        assert!(commit.is_transaction_control());
+        assert!(!commit.is_query());
+    }
+
     mod mysql {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
@@ -438,7 +1249,7 @@ mod tests {
             let expected = SqlQuery::Insert(InsertStatement {
                 table: Table::from("users"),
                 fields: None,
-                data: vec![vec![42.into(), "test".into()]],
+                data: InsertData::Values(vec![vec![42.into(), "test".into()]]),
                 ..Default::default()
             });
             let mut h0 = DefaultHasher::new();
@@ -502,7 +1313,7 @@ mod tests {
             let expected = SqlQuery::Insert(InsertStatement {
                 table: Table::from("users"),
                 fields: None,
-                data: vec![vec![42.into(), "test".into()]],
+                data: InsertData::Values(vec![vec![42.into(), "test".into()]]),
                 ..Default::default()
             });
             let mut h0 = DefaultHasher::new();